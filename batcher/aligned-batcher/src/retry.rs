@@ -1,8 +1,7 @@
-use backon::ExponentialBuilder;
-use backon::Retryable;
 use ethers::prelude::*;
-use futures_util::{stream::SplitSink, SinkExt};
+use futures_util::{future::join_all, stream::SplitSink, SinkExt};
 use log::warn;
+use rand::Rng;
 use std::sync::Arc;
 use std::{future::Future, time::Duration};
 use tokio::net::TcpStream;
@@ -15,6 +14,36 @@ use crate::eth::payment_service::BatcherPaymentService;
 pub const DEFAULT_MIN_DELAY: u64 = 2000;
 pub const DEFAULT_MAX_TIMES: usize = 3;
 pub const DEFAULT_FACTOR: f32 = 2.0;
+pub const DEFAULT_MAX_DELAY: u64 = 30_000;
+
+/// The schedule `retry_function` follows between attempts.
+#[derive(Debug, Clone, Copy)]
+pub enum BackoffStrategy {
+    /// The original `min_delay * factor^n` curve.
+    Exponential,
+    /// AWS-style "decorrelated jitter": each sleep is random in `[min_delay, previous * factor]`,
+    /// capped at `max_delay`, so retrying workers don't lock-step a just-recovered endpoint.
+    DecorrelatedJitter,
+}
+
+/// Source of randomness for [`BackoffStrategy::DecorrelatedJitter`], so tests can supply a
+/// deterministic implementation instead of a real RNG.
+pub trait RetryRng {
+    /// Returns a duration sampled uniformly from `[low, high]` (or `low` if `high <= low`).
+    fn gen_range(&self, low: Duration, high: Duration) -> Duration;
+}
+
+/// The default [`RetryRng`], backed by `rand`'s thread-local RNG.
+pub struct ThreadRetryRng;
+
+impl RetryRng for ThreadRetryRng {
+    fn gen_range(&self, low: Duration, high: Duration) -> Duration {
+        if high <= low {
+            return low;
+        }
+        Duration::from_millis(rand::thread_rng().gen_range(low.as_millis() as u64..=high.as_millis() as u64))
+    }
+}
 
 #[derive(Debug)]
 pub enum RetryError<E> {
@@ -42,99 +71,427 @@ impl<E> RetryError<E> {
 
 impl<E: std::fmt::Display> std::error::Error for RetryError<E> where E: std::fmt::Debug {}
 
-pub async fn retry_function<FutureFn, Fut, T, E>(
-    function: FutureFn,
+/// How a [`QuorumProviders`] decides on a single value out of its concurrent responses.
+#[derive(Debug, Clone)]
+pub enum QuorumPolicy {
+    /// Once every provider has answered, return the first successful response in provider
+    /// order.
+    FirstSuccess,
+    /// Group equal responses together and return once a strict majority of providers agree.
+    Majority,
+    /// Like `Majority`, but providers are weighted individually and the chosen value must
+    /// accumulate at least `min_weight`.
+    Weighted { weights: Vec<u64>, min_weight: u64 },
+}
+
+/// An ordered set of interchangeable providers queried concurrently, resolving to a single
+/// value according to a [`QuorumPolicy`].
+#[derive(Clone)]
+pub struct QuorumProviders<T> {
+    providers: Vec<T>,
+    policy: QuorumPolicy,
+}
+
+impl<T> QuorumProviders<T> {
+    /// Panics if `policy` is `QuorumPolicy::Weighted` and `weights.len()` doesn't match
+    /// `providers.len()` — each provider must have exactly one weight.
+    pub fn new(providers: Vec<T>, policy: QuorumPolicy) -> Self {
+        if let QuorumPolicy::Weighted { weights, .. } = &policy {
+            assert_eq!(
+                weights.len(),
+                providers.len(),
+                "QuorumPolicy::Weighted requires exactly one weight per provider"
+            );
+        }
+        Self { providers, policy }
+    }
+
+    /// Convenience constructor for the common case of just wanting the first provider that
+    /// answers successfully, e.g. a primary endpoint plus one or more fallbacks.
+    pub fn first_success(providers: Vec<T>) -> Self {
+        Self::new(providers, QuorumPolicy::FirstSuccess)
+    }
+
+    pub fn len(&self) -> usize {
+        self.providers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.providers.is_empty()
+    }
+
+    /// Issues `call` against every provider concurrently and resolves the responses according
+    /// to `self.policy`. Returns `RetryError::Transient` when no value crosses the quorum
+    /// threshold, carrying the error of the last provider (in order) that failed, or
+    /// `E::default()` if there were no
+    /// providers or every provider succeeded but the votes were split.
+    pub async fn call<'p, F, Fut, V, E>(&'p self, call: F) -> Result<V, RetryError<E>>
+    where
+        F: Fn(&'p T) -> Fut,
+        Fut: Future<Output = Result<V, E>> + 'p,
+        V: Clone + PartialEq,
+        E: Default,
+    {
+        let results = join_all(self.providers.iter().map(call)).await;
+
+        let quorum_value = match &self.policy {
+            QuorumPolicy::FirstSuccess => results.iter().find_map(|r| r.as_ref().ok()).cloned(),
+            QuorumPolicy::Majority => {
+                let min_weight = self.providers.len() as u64 / 2 + 1;
+                let weights = vec![1u64; results.len()];
+                grouped_quorum_value(&results, &weights, min_weight)
+            }
+            QuorumPolicy::Weighted { weights, min_weight } => {
+                grouped_quorum_value(&results, weights, *min_weight)
+            }
+        };
+
+        if let Some(value) = quorum_value {
+            return Ok(value);
+        }
+
+        let last_error = results
+            .into_iter()
+            .rev()
+            .find_map(|r| r.err())
+            .unwrap_or_default();
+        Err(RetryError::Transient(last_error))
+    }
+}
+
+/// Groups the `Ok` results by equal value, accumulating the weight of each group, and returns
+/// the first value whose accumulated weight reaches `min_weight`.
+fn grouped_quorum_value<V: Clone + PartialEq, E>(
+    results: &[Result<V, E>],
+    weights: &[u64],
+    min_weight: u64,
+) -> Option<V> {
+    let mut groups: Vec<(V, u64)> = Vec::new();
+
+    for (result, weight) in results.iter().zip(weights) {
+        let Ok(value) = result else {
+            continue;
+        };
+
+        match groups.iter_mut().find(|(v, _)| v == value) {
+            Some((_, group_weight)) => *group_weight += weight,
+            None => groups.push((value.clone(), *weight)),
+        }
+    }
+
+    groups
+        .into_iter()
+        .find(|(_, weight)| *weight >= min_weight)
+        .map(|(value, _)| value)
+}
+
+/// How long `retry_function` should wait before the next attempt, as decided by a
+/// [`RetryClassifier`] after inspecting a transient error.
+#[derive(Debug, Clone, Copy)]
+pub enum Backoff {
+    /// No hint was found in the error; fall back to the configured [`BackoffStrategy`].
+    Default,
+    /// The endpoint signaled a rate limit. `retry_after`, when present, overrides the next
+    /// sleep duration (capped at `max_delay`) instead of following the configured
+    /// [`BackoffStrategy`].
+    RateLimited { retry_after: Option<Duration> },
+    /// The error isn't worth retrying at all.
+    Permanent,
+}
+
+/// Inspects a provider error to decide how `retry_function` should back off before the next
+/// attempt.
+pub trait RetryClassifier<E> {
+    fn classify(&self, error: &E) -> Backoff;
+}
+
+/// Always defers to the configured [`BackoffStrategy`].
+pub struct NeverRateLimited;
+
+impl<E> RetryClassifier<E> for NeverRateLimited {
+    fn classify(&self, _error: &E) -> Backoff {
+        Backoff::Default
+    }
+}
+
+const RATE_LIMIT_JSON_RPC_CODES: [&str; 2] = ["-32005", "-32016"];
+
+/// Recognizes HTTP 429 responses and the well-known JSON-RPC rate-limit error codes, extracting
+/// a `Retry-After` hint from the error text when the transport surfaced one.
+pub struct JsonRpcRateLimitClassifier;
+
+impl RetryClassifier<String> for JsonRpcRateLimitClassifier {
+    fn classify(&self, error: &String) -> Backoff {
+        let lower = error.to_lowercase();
+        let is_rate_limited = error.contains("429")
+            || lower.contains("too many requests")
+            || lower.contains("rate limit")
+            || lower.contains("limit exceeded")
+            || RATE_LIMIT_JSON_RPC_CODES
+                .iter()
+                .any(|code| error.contains(code));
+
+        if !is_rate_limited {
+            return Backoff::Default;
+        }
+
+        Backoff::RateLimited {
+            retry_after: parse_retry_after(error),
+        }
+    }
+}
+
+/// Finds the byte offset of `needle` in `haystack` using an ASCII case-insensitive comparison,
+/// without lowercasing `haystack` first. `to_lowercase()` can change a string's byte length for
+/// non-ASCII input (e.g. `İ`), which would make an offset found in a lowercased copy invalid —
+/// or simply wrong — when used to slice the original string.
+fn find_ascii_case_insensitive(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+/// Best-effort extraction of a `Retry-After` value (seconds or an HTTP-date, per RFC 7231) from
+/// a provider error's text. Returns `None` when no such hint is present so the caller falls back
+/// to the configured [`BackoffStrategy`].
+fn parse_retry_after(error: &str) -> Option<Duration> {
+    let marker_pos = find_ascii_case_insensitive(error, "retry-after")?;
+    let rest = &error[marker_pos + "retry-after".len()..];
+    let value = rest.trim_start_matches([':', ' ', '"']);
+
+    // Try the seconds form first: take only the leading digits, so trailing punctuation like
+    // the closing `)` in `"(Retry-After: 30)"` doesn't make the parse fail.
+    let digit_count = value.bytes().take_while(u8::is_ascii_digit).count();
+    if digit_count > 0 {
+        if let Ok(seconds) = value[..digit_count].parse::<u64>() {
+            return Some(Duration::from_secs(seconds));
+        }
+    }
+
+    // Otherwise this is an HTTP-date, which itself contains commas (e.g. "Wed, 21 Oct 2015
+    // 07:28:00 GMT"), so only trim surrounding wrapper characters rather than splitting on them.
+    let value = value.trim_end_matches([')', ']', '"', '}', '\n']).trim();
+    let retry_at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let delta = retry_at.with_timezone(&chrono::Utc) - chrono::Utc::now();
+    delta.to_std().ok()
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn retry_function<FutureFn, Fut, T, E, C, R>(
+    mut function: FutureFn,
+    classifier: &C,
+    rng: &R,
     min_delay: u64,
+    max_delay: u64,
     factor: f32,
     max_times: usize,
+    strategy: BackoffStrategy,
 ) -> Result<T, RetryError<E>>
 where
     Fut: Future<Output = Result<T, RetryError<E>>>,
     FutureFn: FnMut() -> Fut,
+    C: RetryClassifier<E>,
+    R: RetryRng,
 {
-    let backoff = ExponentialBuilder::default()
-        .with_min_delay(Duration::from_millis(min_delay))
-        .with_max_times(max_times)
-        .with_factor(factor);
-
-    function
-        .retry(backoff)
-        .sleep(tokio::time::sleep)
-        .when(|e| matches!(e, RetryError::Transient(_)))
-        .await
+    let min_delay = Duration::from_millis(min_delay);
+    let max_delay = Duration::from_millis(max_delay);
+    let mut attempt: usize = 0;
+    // Only used by `BackoffStrategy::DecorrelatedJitter`: the previous sleep, which the next
+    // one is derived from.
+    let mut jitter_sleep = min_delay;
+
+    loop {
+        match function().await {
+            Ok(value) => return Ok(value),
+            Err(RetryError::Permanent(e)) => return Err(RetryError::Permanent(e)),
+            Err(RetryError::Transient(e)) => {
+                attempt += 1;
+                if attempt >= max_times {
+                    return Err(RetryError::Transient(e));
+                }
+
+                let delay = match classifier.classify(&e) {
+                    Backoff::Permanent => return Err(RetryError::Permanent(e)),
+                    Backoff::RateLimited {
+                        retry_after: Some(retry_after),
+                    } => retry_after.min(max_delay),
+                    Backoff::RateLimited { retry_after: None } | Backoff::Default => {
+                        match strategy {
+                            BackoffStrategy::Exponential => Duration::from_secs_f32(
+                                min_delay.as_secs_f32() * factor.powi(attempt as i32 - 1),
+                            )
+                            .min(max_delay),
+                            BackoffStrategy::DecorrelatedJitter => {
+                                jitter_sleep = rng
+                                    .gen_range(min_delay, jitter_sleep.mul_f32(factor))
+                                    .min(max_delay);
+                                jitter_sleep
+                            }
+                        }
+                    }
+                };
+
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
 }
 
 pub async fn get_user_balance_retryable(
-    payment_service: &BatcherPaymentService,
-    payment_service_fallback: &BatcherPaymentService,
+    payment_services: &QuorumProviders<BatcherPaymentService>,
     addr: &Address,
 ) -> Result<U256, RetryError<String>> {
-    if let Ok(balance) = payment_service.user_balances(*addr).call().await {
-        return Ok(balance);
-    };
-
-    payment_service_fallback
-        .user_balances(*addr)
-        .call()
+    let addr = *addr;
+    payment_services
+        .call(move |payment_service| async move {
+            payment_service
+                .user_balances(addr)
+                .call()
+                .await
+                .map_err(|e| e.to_string())
+        })
         .await
         .map_err(|e| {
             warn!("Failed to get balance for address {:?}. Error: {e}", addr);
-            RetryError::Transient(e.to_string())
+            e
         })
 }
 
 pub async fn get_user_nonce_from_ethereum_retryable(
-    payment_service: &BatcherPaymentService,
-    payment_service_fallback: &BatcherPaymentService,
+    payment_services: &QuorumProviders<BatcherPaymentService>,
     addr: Address,
 ) -> Result<U256, RetryError<String>> {
-    if let Ok(nonce) = payment_service.user_nonces(addr).call().await {
-        return Ok(nonce);
-    }
-    payment_service_fallback
-        .user_nonces(addr)
-        .call()
+    payment_services
+        .call(move |payment_service| async move {
+            payment_service
+                .user_nonces(addr)
+                .call()
+                .await
+                .map_err(|e| e.to_string())
+        })
         .await
         .map_err(|e| {
             warn!("Error getting user nonce: {e}");
-            RetryError::Transient(e.to_string())
+            e
         })
 }
 
 pub async fn user_balance_is_unlocked_retryable(
-    payment_service: &BatcherPaymentService,
-    payment_service_fallback: &BatcherPaymentService,
+    payment_services: &QuorumProviders<BatcherPaymentService>,
     addr: &Address,
 ) -> Result<bool, RetryError<()>> {
-    if let Ok(unlock_block) = payment_service.user_unlock_block(*addr).call().await {
-        return Ok(unlock_block != U256::zero());
-    }
-    if let Ok(unlock_block) = payment_service_fallback
-        .user_unlock_block(*addr)
-        .call()
+    let addr = *addr;
+    payment_services
+        .call(move |payment_service| async move {
+            payment_service
+                .user_unlock_block(addr)
+                .call()
+                .await
+                .map(|unlock_block| unlock_block != U256::zero())
+                .map_err(|_| ())
+        })
         .await
-    {
-        return Ok(unlock_block != U256::zero());
-    }
-    warn!("Failed to get user locking state {:?}", addr);
-    Err(RetryError::Transient(()))
+        .map_err(|e| {
+            warn!("Failed to get user locking state {:?}", addr);
+            e
+        })
 }
 
 pub async fn get_gas_price_retryable(
-    eth_ws_provider: &Provider<Http>,
-    eth_ws_provider_fallback: &Provider<Http>,
+    eth_providers: &QuorumProviders<Provider<Http>>,
 ) -> Result<U256, RetryError<String>> {
-    if let Ok(gas_price) = eth_ws_provider
-        .get_gas_price()
+    eth_providers
+        .call(|provider| async move { provider.get_gas_price().await.map_err(|e| e.to_string()) })
         .await
-        .inspect_err(|e| warn!("Failed to get gas price. Trying with fallback: {e:?}"))
-    {
-        return Ok(gas_price);
+        .map_err(|e| {
+            warn!("Failed to get gas price from all providers: {e:?}");
+            e
+        })
+}
+
+const FEE_HISTORY_BLOCK_COUNT: u64 = 20;
+const FEE_HISTORY_REWARD_PERCENTILES: [f64; 3] = [10.0, 50.0, 90.0];
+const PRIORITY_FEE_PERCENTILE_INDEX: usize = 1; // corresponds to the 50th percentile above
+const MIN_PRIORITY_FEE_PER_GAS: u64 = 1_000_000_000; // 1 gwei
+const BASE_FEE_MULTIPLIER: u64 = 2;
+
+/// EIP-1559 fee parameters for a transaction, as estimated from recent block history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeEstimation {
+    pub max_fee_per_gas: U256,
+    pub max_priority_fee_per_gas: U256,
+}
+
+/// Estimates EIP-1559 fees from `eth_feeHistory` instead of the legacy `eth_gasPrice`, so
+/// batch-submission transactions keep up with base-fee growth instead of getting stuck.
+///
+/// `max_priority_fee_per_gas` is the median of the 50th-percentile priority fee paid over the
+/// last `FEE_HISTORY_BLOCK_COUNT` blocks, floored at `MIN_PRIORITY_FEE_PER_GAS`.
+/// `max_fee_per_gas` cushions the next block's base fee by `BASE_FEE_MULTIPLIER` to absorb a
+/// few blocks of base-fee growth, plus the priority fee.
+pub async fn get_fee_estimation_retryable(
+    eth_providers: &QuorumProviders<Provider<Http>>,
+) -> Result<FeeEstimation, RetryError<String>> {
+    eth_providers
+        .call(|provider| async move { fee_estimation(provider).await })
+        .await
+        .map_err(|e| {
+            warn!("Failed to get fee estimation from all providers: {e}");
+            e
+        })
+}
+
+/// Generic over `Middleware` (rather than `Provider<Http>` directly) so it can be exercised in
+/// tests against `Provider<MockProvider>` without a live RPC endpoint.
+async fn fee_estimation<M: Middleware>(provider: &M) -> Result<FeeEstimation, String>
+where
+    M::Error: std::fmt::Display,
+{
+    let fee_history = provider
+        .fee_history(
+            FEE_HISTORY_BLOCK_COUNT,
+            BlockNumber::Latest,
+            &FEE_HISTORY_REWARD_PERCENTILES,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let Some(base_fee_next) = fee_history.base_fee_per_gas.last().copied() else {
+        return legacy_fee_estimation(provider).await;
+    };
+
+    let mut priority_fees: Vec<U256> = fee_history
+        .reward
+        .iter()
+        .filter_map(|row| row.get(PRIORITY_FEE_PERCENTILE_INDEX).copied())
+        .collect();
+
+    if priority_fees.is_empty() {
+        return legacy_fee_estimation(provider).await;
     }
 
-    eth_ws_provider_fallback.get_gas_price().await.map_err(|e| {
-        warn!("Failed to get fallback gas price: {e:?}");
-        RetryError::Transient(e.to_string())
+    priority_fees.sort();
+    let max_priority_fee_per_gas =
+        priority_fees[priority_fees.len() / 2].max(U256::from(MIN_PRIORITY_FEE_PER_GAS));
+    let max_fee_per_gas = base_fee_next * BASE_FEE_MULTIPLIER + max_priority_fee_per_gas;
+
+    Ok(FeeEstimation {
+        max_fee_per_gas,
+        max_priority_fee_per_gas,
+    })
+}
+
+/// Falls back to `eth_gasPrice` for chains or blocks that don't expose EIP-1559 fee history
+/// (e.g. pre-1559 chains, or an empty reward column).
+async fn legacy_fee_estimation<M: Middleware>(provider: &M) -> Result<FeeEstimation, String>
+where
+    M::Error: std::fmt::Display,
+{
+    let gas_price = provider.get_gas_price().await.map_err(|e| e.to_string())?;
+    Ok(FeeEstimation {
+        max_fee_per_gas: gas_price,
+        max_priority_fee_per_gas: gas_price,
     })
 }
 
@@ -166,7 +523,8 @@ mod test {
     };
     use ethers::{
         contract::abigen,
-        types::{Address, U256},
+        providers::MockProvider,
+        types::{Address, FeeHistory, U256},
         utils::{Anvil, AnvilInstance},
     };
     use futures_util::StreamExt;
@@ -181,6 +539,170 @@ mod test {
         "../aligned-sdk/abi/BatcherPaymentService.json"
     );
 
+    #[tokio::test]
+    async fn test_quorum_providers_call_with_no_providers_is_transient() {
+        let providers: QuorumProviders<()> = QuorumProviders::first_success(vec![]);
+        let result = providers.call(|_| async { Ok::<u8, String>(1) }).await;
+        assert!(matches!(result, Err(RetryError::Transient(e)) if e.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn test_quorum_providers_majority_picks_value_with_strict_majority() {
+        let providers = QuorumProviders::new(vec![1u8, 2u8, 3u8], QuorumPolicy::Majority);
+        let result = providers
+            .call(|provider| async move {
+                if *provider == 3 {
+                    Err("disagreeing provider".to_string())
+                } else {
+                    Ok("value")
+                }
+            })
+            .await;
+        assert_eq!(result.unwrap(), "value");
+    }
+
+    #[tokio::test]
+    async fn test_quorum_providers_weighted_below_min_weight_is_transient() {
+        let providers = QuorumProviders::new(
+            vec![1u8, 2u8, 3u8],
+            QuorumPolicy::Weighted {
+                weights: vec![2, 2, 2],
+                min_weight: 5,
+            },
+        );
+        let result = providers
+            .call(|provider| async move { Ok::<u8, String>(*provider) })
+            .await;
+        assert!(matches!(result, Err(RetryError::Transient(_))));
+    }
+
+    #[test]
+    #[should_panic(expected = "QuorumPolicy::Weighted requires exactly one weight per provider")]
+    fn test_quorum_providers_new_panics_on_mismatched_weights() {
+        let _ = QuorumProviders::new(
+            vec![1u8, 2u8],
+            QuorumPolicy::Weighted {
+                weights: vec![1],
+                min_weight: 1,
+            },
+        );
+    }
+
+    #[test]
+    fn test_json_rpc_rate_limit_classifier_detects_http_429() {
+        let error = "server returned an error response: error code 429: Too Many Requests"
+            .to_string();
+        assert!(matches!(
+            JsonRpcRateLimitClassifier.classify(&error),
+            Backoff::RateLimited { .. }
+        ));
+    }
+
+    #[test]
+    fn test_json_rpc_rate_limit_classifier_detects_json_rpc_codes() {
+        for code in ["-32005", "-32016"] {
+            let error = format!("(code: {code}, message: \"limit exceeded\")");
+            assert!(matches!(
+                JsonRpcRateLimitClassifier.classify(&error),
+                Backoff::RateLimited { .. }
+            ));
+        }
+    }
+
+    #[test]
+    fn test_json_rpc_rate_limit_classifier_defaults_on_unrelated_error() {
+        let error = "connection refused".to_string();
+        assert!(matches!(
+            JsonRpcRateLimitClassifier.classify(&error),
+            Backoff::Default
+        ));
+    }
+
+    #[test]
+    fn test_parse_retry_after_seconds() {
+        let error = "429 Too Many Requests (Retry-After: 30)";
+        assert_eq!(parse_retry_after(error), Some(Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn test_parse_retry_after_is_case_insensitive_and_byte_offset_safe() {
+        // "İ" (U+0130) lowercases to "i̇" (two chars), growing the string by a byte. The lookup
+        // must not use an offset computed against a lowercased copy to slice the original error.
+        let error = "İ rate limited (RETRY-AFTER: 7)";
+        assert_eq!(parse_retry_after(error), Some(Duration::from_secs(7)));
+    }
+
+    #[test]
+    fn test_parse_retry_after_http_date() {
+        let error = "429 Too Many Requests (Retry-After: Wed, 21 Oct 2015 07:28:00 GMT)";
+        // The date is in the past relative to "now", so no positive duration can be derived
+        // from it; this only asserts parsing doesn't panic and yields a deterministic `None`.
+        assert_eq!(parse_retry_after(error), None);
+    }
+
+    #[test]
+    fn test_parse_retry_after_http_date_in_the_future_is_parsed_despite_comma() {
+        // The HTTP-date itself contains a comma ("Wed, 21 ..."), so the delimiter used to find
+        // the end of the value must not treat `,` as a hard stop.
+        let future = chrono::Utc::now() + chrono::Duration::seconds(120);
+        let error = format!(
+            "429 Too Many Requests (Retry-After: {})",
+            future.to_rfc2822()
+        );
+        let duration = parse_retry_after(&error).expect("expected a parsed duration");
+        assert!(duration.as_secs() > 100 && duration.as_secs() <= 120);
+    }
+
+    #[test]
+    fn test_parse_retry_after_missing_hint_returns_none() {
+        let error = "429 Too Many Requests";
+        assert_eq!(parse_retry_after(error), None);
+    }
+
+    /// Deterministic [`RetryRng`] that always returns the top of the requested range, so the
+    /// decorrelated-jitter schedule below is reproducible instead of actually random.
+    struct MaxRng;
+
+    impl RetryRng for MaxRng {
+        fn gen_range(&self, low: Duration, high: Duration) -> Duration {
+            high.max(low)
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_function_decorrelated_jitter_follows_schedule_and_caps_at_max_delay() {
+        let mut attempts = 0u32;
+        let start = tokio::time::Instant::now();
+
+        let result: Result<(), RetryError<String>> = retry_function(
+            || {
+                attempts += 1;
+                let attempt = attempts;
+                async move {
+                    if attempt < 4 {
+                        Err(RetryError::Transient("still failing".to_string()))
+                    } else {
+                        Ok(())
+                    }
+                }
+            },
+            &NeverRateLimited,
+            &MaxRng,
+            100,
+            250,
+            2.0,
+            10,
+            BackoffStrategy::DecorrelatedJitter,
+        )
+        .await;
+
+        assert!(result.is_ok());
+        assert_eq!(attempts, 4);
+        // With MaxRng always returning the range's upper bound: 100*2=200ms, then
+        // min(200*2, 250)=250ms, then min(250*2, 250)=250ms.
+        assert_eq!(start.elapsed(), Duration::from_millis(200 + 250 + 250));
+    }
+
     async fn setup_anvil(port: u16) -> (AnvilInstance, BatcherPaymentService) {
         let anvil = Anvil::new()
             .port(port)
@@ -209,142 +731,215 @@ mod test {
 
     #[tokio::test]
     async fn test_get_user_balance_retryable() {
-        let payment_service;
+        let payment_services;
         let dummy_user_addr =
             Address::from_str("0x8969c5eD335650692Bc04293B07F5BF2e7A673C0").unwrap();
         {
             let _anvil;
+            let payment_service;
             (_anvil, payment_service) = setup_anvil(8545u16).await;
+            payment_services = QuorumProviders::first_success(vec![
+                payment_service.clone(),
+                payment_service,
+            ]);
 
-            let balance =
-                get_user_balance_retryable(&payment_service, &payment_service, &dummy_user_addr)
-                    .await
-                    .unwrap();
+            let balance = get_user_balance_retryable(&payment_services, &dummy_user_addr)
+                .await
+                .unwrap();
 
             assert_eq!(balance, U256::zero());
             // Kill anvil
         }
 
-        let result =
-            get_user_balance_retryable(&payment_service, &payment_service, &dummy_user_addr).await;
+        let result = get_user_balance_retryable(&payment_services, &dummy_user_addr).await;
         assert!(matches!(result, Err(RetryError::Transient(_))));
 
         // restart anvil
-        let (_anvil, _) = setup_anvil(8545u16).await;
-        let balance =
-            get_user_balance_retryable(&payment_service, &payment_service, &dummy_user_addr)
-                .await
-                .unwrap();
+        let (_anvil, payment_service) = setup_anvil(8545u16).await;
+        let payment_services =
+            QuorumProviders::first_success(vec![payment_service.clone(), payment_service]);
+        let balance = get_user_balance_retryable(&payment_services, &dummy_user_addr)
+            .await
+            .unwrap();
 
         assert_eq!(balance, U256::zero());
     }
 
     #[tokio::test]
     async fn test_user_balance_is_unlocked_retryable() {
-        let payment_service;
+        let payment_services;
         let dummy_user_addr =
             Address::from_str("0x8969c5eD335650692Bc04293B07F5BF2e7A673C0").unwrap();
 
         {
             let _anvil;
+            let payment_service;
             (_anvil, payment_service) = setup_anvil(8546u16).await;
-            let unlocked = user_balance_is_unlocked_retryable(
-                &payment_service,
-                &payment_service,
-                &dummy_user_addr,
-            )
-            .await
-            .unwrap();
+            payment_services = QuorumProviders::first_success(vec![
+                payment_service.clone(),
+                payment_service,
+            ]);
+            let unlocked = user_balance_is_unlocked_retryable(&payment_services, &dummy_user_addr)
+                .await
+                .unwrap();
 
             assert_eq!(unlocked, false);
             // Kill Anvil
         }
 
-        let result = user_balance_is_unlocked_retryable(
-            &payment_service,
-            &payment_service,
-            &dummy_user_addr,
-        )
-        .await;
+        let result = user_balance_is_unlocked_retryable(&payment_services, &dummy_user_addr).await;
         assert!(matches!(result, Err(RetryError::Transient(_))));
 
         // restart Anvil
         let (_anvil, payment_service) = setup_anvil(8546u16).await;
-        let unlocked = user_balance_is_unlocked_retryable(
-            &payment_service,
-            &payment_service,
-            &dummy_user_addr,
-        )
-        .await
-        .unwrap();
+        let payment_services =
+            QuorumProviders::first_success(vec![payment_service.clone(), payment_service]);
+        let unlocked = user_balance_is_unlocked_retryable(&payment_services, &dummy_user_addr)
+            .await
+            .unwrap();
 
         assert_eq!(unlocked, false);
     }
 
     #[tokio::test]
     async fn test_get_user_nonce_retryable() {
-        let payment_service;
+        let payment_services;
         let dummy_user_addr =
             Address::from_str("0x8969c5eD335650692Bc04293B07F5BF2e7A673C0").unwrap();
         {
             let _anvil;
+            let payment_service;
             (_anvil, payment_service) = setup_anvil(8547u16).await;
-            let nonce = get_user_nonce_from_ethereum_retryable(
-                &payment_service,
-                &payment_service,
-                dummy_user_addr,
-            )
-            .await
-            .unwrap();
+            payment_services = QuorumProviders::first_success(vec![
+                payment_service.clone(),
+                payment_service,
+            ]);
+            let nonce =
+                get_user_nonce_from_ethereum_retryable(&payment_services, dummy_user_addr)
+                    .await
+                    .unwrap();
 
             assert_eq!(nonce, U256::zero());
             // Kill Anvil
         }
 
-        let result = get_user_nonce_from_ethereum_retryable(
-            &payment_service,
-            &payment_service,
-            dummy_user_addr,
-        )
-        .await;
+        let result =
+            get_user_nonce_from_ethereum_retryable(&payment_services, dummy_user_addr).await;
         assert!(matches!(result, Err(RetryError::Transient(_))));
 
         // restart Anvil
         let (_anvil, payment_service) = setup_anvil(8547u16).await;
+        let payment_services =
+            QuorumProviders::first_success(vec![payment_service.clone(), payment_service]);
 
-        let nonce = get_user_nonce_from_ethereum_retryable(
-            &payment_service,
-            &payment_service,
-            dummy_user_addr,
-        )
-        .await
-        .unwrap();
+        let nonce = get_user_nonce_from_ethereum_retryable(&payment_services, dummy_user_addr)
+            .await
+            .unwrap();
 
         assert_eq!(nonce, U256::zero());
     }
 
     #[tokio::test]
     async fn test_get_gas_price_retryable() {
-        let eth_rpc_provider;
+        let eth_providers;
         {
             let (_anvil, _payment_service) = setup_anvil(8548u16).await;
-            eth_rpc_provider = get_provider("http://localhost:8548".to_string())
+            let eth_rpc_provider = get_provider("http://localhost:8548".to_string())
                 .expect("Failed to get ethereum websocket provider");
-            let result = get_gas_price_retryable(&eth_rpc_provider, &eth_rpc_provider).await;
+            eth_providers =
+                QuorumProviders::first_success(vec![eth_rpc_provider.clone(), eth_rpc_provider]);
+            let result = get_gas_price_retryable(&eth_providers).await;
 
             assert!(result.is_ok());
             // kill Anvil
         }
-        let result = get_gas_price_retryable(&eth_rpc_provider, &eth_rpc_provider).await;
+        let result = get_gas_price_retryable(&eth_providers).await;
         assert!(matches!(result, Err(RetryError::Transient(_))));
 
         // restart Anvil
         let (_anvil, _payment_service) = setup_anvil(8548u16).await;
-        let result = get_gas_price_retryable(&eth_rpc_provider, &eth_rpc_provider).await;
+        let result = get_gas_price_retryable(&eth_providers).await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_get_fee_estimation_retryable() {
+        let eth_providers;
+        {
+            let (_anvil, _payment_service) = setup_anvil(8549u16).await;
+            let eth_rpc_provider = get_provider("http://localhost:8549".to_string())
+                .expect("Failed to get ethereum websocket provider");
+            eth_providers =
+                QuorumProviders::first_success(vec![eth_rpc_provider.clone(), eth_rpc_provider]);
+            let result = get_fee_estimation_retryable(&eth_providers).await.unwrap();
+
+            assert!(result.max_fee_per_gas >= result.max_priority_fee_per_gas);
+            // kill Anvil
+        }
+        let result = get_fee_estimation_retryable(&eth_providers).await;
+        assert!(matches!(result, Err(RetryError::Transient(_))));
+
+        // restart Anvil
+        let (_anvil, _payment_service) = setup_anvil(8549u16).await;
+        let result = get_fee_estimation_retryable(&eth_providers).await;
 
         assert!(result.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_fee_estimation_falls_back_to_legacy_on_empty_reward_rows() {
+        let (provider, mock) = Provider::<MockProvider>::mocked();
+
+        let empty_reward_history = FeeHistory {
+            base_fee_per_gas: vec![U256::from(1_000_000_000u64)],
+            gas_used_ratio: vec![],
+            oldest_block: U256::zero(),
+            reward: vec![],
+        };
+        let legacy_gas_price = U256::from(5_000_000_000u64);
+
+        // MockProvider serves responses in the reverse order they're pushed, so push the
+        // second call's response (eth_gasPrice, from the legacy fallback) before the first
+        // call's (eth_feeHistory).
+        mock.push(legacy_gas_price).unwrap();
+        mock.push(empty_reward_history).unwrap();
+
+        let result = fee_estimation(&provider).await.unwrap();
+
+        assert_eq!(result.max_fee_per_gas, legacy_gas_price);
+        assert_eq!(result.max_priority_fee_per_gas, legacy_gas_price);
+    }
+
+    #[tokio::test]
+    async fn test_fee_estimation_uses_fee_history_when_reward_rows_are_present() {
+        let (provider, mock) = Provider::<MockProvider>::mocked();
+
+        let base_fee_next = U256::from(10_000_000_000u64);
+        let fee_history = FeeHistory {
+            base_fee_per_gas: vec![U256::from(8_000_000_000u64), base_fee_next],
+            gas_used_ratio: vec![],
+            oldest_block: U256::zero(),
+            reward: vec![
+                vec![U256::zero(), U256::from(2_000_000_000u64), U256::zero()],
+                vec![U256::zero(), U256::from(3_000_000_000u64), U256::zero()],
+            ],
+        };
+        mock.push(fee_history).unwrap();
+
+        let result = fee_estimation(&provider).await.unwrap();
+
+        // Median of the 50th-percentile column [2 gwei, 3 gwei] is 3 gwei (`len / 2` index).
+        assert_eq!(
+            result.max_priority_fee_per_gas,
+            U256::from(3_000_000_000u64)
+        );
+        assert_eq!(
+            result.max_fee_per_gas,
+            base_fee_next * BASE_FEE_MULTIPLIER + U256::from(3_000_000_000u64)
+        );
+    }
+
     #[tokio::test]
     async fn test_send_response_retryable() {
         let listener = TcpListener::bind("localhost:8553").await.unwrap();
@@ -377,4 +972,4 @@ mod test {
         assert!(result.is_ok());
         client_handle.await.unwrap()
     }
-}
\ No newline at end of file
+}
@@ -1,7 +1,10 @@
 use lambdaworks_crypto::merkle_tree::merkle::MerkleTree;
+use lambdaworks_crypto::merkle_tree::proof::Proof;
 use batcher::types::{VerificationCommitmentBatch, VerificationData};
 
 const MAX_BATCH_SIZE: usize = 2 * 1024 * 1024 * 10;
+// 32 bytes per sibling hash, enough for a batch of up to 2^64 leaves.
+const MAX_PROOF_SIZE: usize = 32 * 64;
 
 #[no_mangle]
 pub extern "C" fn verify_merkle_tree_batch_ffi(
@@ -29,6 +32,84 @@ pub extern "C" fn verify_merkle_tree_batch_ffi(
     }
 }
 
+/// Writes the inclusion proof for the leaf at `leaf_index` into `out_proof_bytes` as consecutive
+/// 32-byte sibling hashes, with the proof's byte length written to `out_proof_len`.
+#[no_mangle]
+pub extern "C" fn generate_merkle_proof_ffi(
+    batch_bytes: &[u8; MAX_BATCH_SIZE],
+    batch_len: u32,
+    leaf_index: usize,
+    out_proof_bytes: &mut [u8; MAX_PROOF_SIZE],
+    out_proof_len: &mut u32,
+) -> bool {
+    let batch = match serde_json::from_slice::<Vec<VerificationData>>(&batch_bytes[..batch_len as usize]) {
+        Ok(batch) => batch,
+        Err(_) => {
+            eprintln!("Failed to parse batch data");
+            return false;
+        }
+    };
+
+    if leaf_index >= batch.len() {
+        eprintln!(
+            "leaf_index {} out of bounds for batch of size {}",
+            leaf_index,
+            batch.len()
+        );
+        return false;
+    }
+
+    let batch_commitment = VerificationCommitmentBatch::from(&batch);
+    let batch_merkle_tree: MerkleTree<VerificationCommitmentBatch> = MerkleTree::build(&batch_commitment.0);
+
+    let Some(proof) = batch_merkle_tree.get_proof_by_pos(leaf_index) else {
+        eprintln!("Failed to build merkle proof for leaf index {}", leaf_index);
+        return false;
+    };
+
+    let proof_len = proof.merkle_path.len() * 32;
+    if proof_len > MAX_PROOF_SIZE {
+        eprintln!("Merkle proof is too large to fit in the output buffer");
+        return false;
+    }
+
+    for (i, node) in proof.merkle_path.iter().enumerate() {
+        out_proof_bytes[i * 32..(i + 1) * 32].copy_from_slice(node);
+    }
+    *out_proof_len = proof_len as u32;
+
+    true
+}
+
+/// Recomputes a Merkle root from `leaf_commitment` and `proof_bytes` and compares it against
+/// `merkle_root`.
+#[no_mangle]
+pub extern "C" fn verify_merkle_proof_ffi(
+    leaf_commitment: &[u8; 32],
+    proof_bytes: &[u8; MAX_PROOF_SIZE],
+    proof_len: u32,
+    leaf_index: usize,
+    merkle_root: &[u8; 32],
+) -> bool {
+    if proof_len as usize % 32 != 0 || proof_len as usize > MAX_PROOF_SIZE {
+        eprintln!("Invalid merkle proof length: {}", proof_len);
+        return false;
+    }
+
+    let merkle_path: Vec<[u8; 32]> = proof_bytes[..proof_len as usize]
+        .chunks_exact(32)
+        .map(|chunk| {
+            let mut node = [0u8; 32];
+            node.copy_from_slice(chunk);
+            node
+        })
+        .collect();
+
+    let proof = Proof { merkle_path };
+
+    proof.verify::<VerificationCommitmentBatch>(merkle_root, leaf_index, leaf_commitment)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -73,4 +154,124 @@ mod tests {
         let result = verify_merkle_tree_batch_ffi(&bytes, bytes_vec.len() as u32, &merkle_root);
         assert!(!result);
     }
+
+    #[test]
+    fn test_generate_and_verify_merkle_proof_ffi() {
+        let path = "./test_files/7a3d9215cfac21a4b0e94382e53a9f26bc23ed990f9c850a31ccf3a65aec1466.json";
+        let mut file = File::open(path).unwrap();
+        let mut bytes_vec = Vec::new();
+        file.read_to_end(&mut bytes_vec).unwrap();
+
+        let mut bytes = [0; MAX_BATCH_SIZE];
+        bytes[..bytes_vec.len()].copy_from_slice(&bytes_vec);
+
+        let mut merkle_root = [0; 32];
+        merkle_root.copy_from_slice(&hex::decode("7a3d9215cfac21a4b0e94382e53a9f26bc23ed990f9c850a31ccf3a65aec1466").unwrap());
+
+        let leaf_index = 0usize;
+        let mut proof_bytes = [0u8; MAX_PROOF_SIZE];
+        let mut proof_len = 0u32;
+
+        let generated = generate_merkle_proof_ffi(
+            &bytes,
+            bytes_vec.len() as u32,
+            leaf_index,
+            &mut proof_bytes,
+            &mut proof_len,
+        );
+        assert!(generated);
+
+        let batch: Vec<VerificationData> =
+            serde_json::from_slice(&bytes_vec).expect("Failed to parse batch data");
+        let batch_commitment = VerificationCommitmentBatch::from(&batch);
+        let leaf_commitment = batch_commitment.0[leaf_index];
+
+        let verified = verify_merkle_proof_ffi(
+            &leaf_commitment,
+            &proof_bytes,
+            proof_len,
+            leaf_index,
+            &merkle_root,
+        );
+        assert!(verified);
+    }
+
+    #[test]
+    fn test_verify_merkle_proof_ffi_bad_leaf() {
+        let path = "./test_files/7a3d9215cfac21a4b0e94382e53a9f26bc23ed990f9c850a31ccf3a65aec1466.json";
+        let mut file = File::open(path).unwrap();
+        let mut bytes_vec = Vec::new();
+        file.read_to_end(&mut bytes_vec).unwrap();
+
+        let mut bytes = [0; MAX_BATCH_SIZE];
+        bytes[..bytes_vec.len()].copy_from_slice(&bytes_vec);
+
+        let mut merkle_root = [0; 32];
+        merkle_root.copy_from_slice(&hex::decode("7a3d9215cfac21a4b0e94382e53a9f26bc23ed990f9c850a31ccf3a65aec1466").unwrap());
+
+        let leaf_index = 0usize;
+        let mut proof_bytes = [0u8; MAX_PROOF_SIZE];
+        let mut proof_len = 0u32;
+
+        assert!(generate_merkle_proof_ffi(
+            &bytes,
+            bytes_vec.len() as u32,
+            leaf_index,
+            &mut proof_bytes,
+            &mut proof_len,
+        ));
+
+        let mut bad_leaf_commitment = [0u8; 32];
+        bad_leaf_commitment[0] ^= 0x01;
+
+        let verified = verify_merkle_proof_ffi(
+            &bad_leaf_commitment,
+            &proof_bytes,
+            proof_len,
+            leaf_index,
+            &merkle_root,
+        );
+        assert!(!verified);
+    }
+
+    #[test]
+    fn test_generate_merkle_proof_ffi_out_of_bounds() {
+        let path = "./test_files/7a3d9215cfac21a4b0e94382e53a9f26bc23ed990f9c850a31ccf3a65aec1466.json";
+        let mut file = File::open(path).unwrap();
+        let mut bytes_vec = Vec::new();
+        file.read_to_end(&mut bytes_vec).unwrap();
+
+        let mut bytes = [0; MAX_BATCH_SIZE];
+        bytes[..bytes_vec.len()].copy_from_slice(&bytes_vec);
+
+        let batch: Vec<VerificationData> =
+            serde_json::from_slice(&bytes_vec).expect("Failed to parse batch data");
+
+        let mut proof_bytes = [0u8; MAX_PROOF_SIZE];
+        let mut proof_len = 0u32;
+
+        let generated = generate_merkle_proof_ffi(
+            &bytes,
+            bytes_vec.len() as u32,
+            batch.len(),
+            &mut proof_bytes,
+            &mut proof_len,
+        );
+        assert!(!generated);
+    }
+
+    #[test]
+    fn test_verify_merkle_proof_ffi_rejects_oversized_proof_len() {
+        let leaf_commitment = [0u8; 32];
+        let proof_bytes = [0u8; MAX_PROOF_SIZE];
+        let merkle_root = [0u8; 32];
+
+        // Divisible by 32 but larger than MAX_PROOF_SIZE: must be rejected rather than indexing
+        // past the buffer.
+        let proof_len = MAX_PROOF_SIZE as u32 + 32;
+
+        let verified =
+            verify_merkle_proof_ffi(&leaf_commitment, &proof_bytes, proof_len, 0, &merkle_root);
+        assert!(!verified);
+    }
 }
\ No newline at end of file